@@ -1,11 +1,16 @@
-use collections::HashMap;
-use gpui::{AnyElement, IntoElement};
+use collections::{HashMap, HashSet};
+use gpui::{
+    rgb, AnyElement, Empty, FontWeight, HighlightStyle, Hsla, IntoElement, RenderImage, Size,
+    UnderlineStyle,
+};
 use multi_buffer::{Anchor, AnchorRangeExt, MultiBufferRow, MultiBufferSnapshot, ToPoint};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, ops::Range, sync::Arc};
 use sum_tree::{Bias, SeekTarget, SumTree};
 use text::Point;
-use ui::{IconName, SharedString, WindowContext};
+use theme::SyntaxTheme;
+use ui::{px, IconName, Pixels, SharedString, WindowContext};
 
 use crate::FoldPlaceholder;
 
@@ -91,6 +96,100 @@ impl CreaseSnapshot {
         })
     }
 
+    /// Captures every [`Crease::Fold`] as a [`SerializedCrease`] — its point
+    /// offsets plus metadata — so folded regions can be persisted to the
+    /// workspace database and restored with [`CreaseMap::restore`].
+    pub fn serialize(&self, snapshot: &MultiBufferSnapshot) -> Vec<SerializedCrease> {
+        let mut cursor = self.creases.cursor::<ItemSummary>(snapshot);
+        let mut serialized = Vec::new();
+
+        cursor.next(snapshot);
+        while let Some(item) = cursor.item() {
+            if let Crease::Fold {
+                range, metadata, ..
+            } = &item.crease
+            {
+                serialized.push(SerializedCrease {
+                    range: range.start.to_point(snapshot)..range.end.to_point(snapshot),
+                    metadata: metadata.clone(),
+                });
+            }
+            cursor.next(snapshot);
+        }
+
+        serialized
+    }
+
+    /// Returns the direct children of the crease identified by `id` — the
+    /// creases whose tightest enclosing crease was `id` when they were
+    /// inserted. A crease inserted later that encloses existing creases does
+    /// not become their parent.
+    pub fn children<'a>(
+        &'a self,
+        id: CreaseId,
+        _snapshot: &'a MultiBufferSnapshot,
+    ) -> impl 'a + Iterator<Item = &'a Crease> {
+        self.creases
+            .iter()
+            .filter_map(move |item| (item.parent == Some(id)).then_some(&item.crease))
+    }
+
+    /// Returns every crease transitively nested under `id` by the parent links
+    /// recorded at insert time (see [`Self::children`]), for "fold this block
+    /// and all of its sub-blocks" operations.
+    pub fn descendants<'a>(
+        &'a self,
+        id: CreaseId,
+        _snapshot: &'a MultiBufferSnapshot,
+    ) -> impl 'a + Iterator<Item = &'a Crease> {
+        let mut stack = vec![id];
+        let mut descendants = Vec::new();
+        while let Some(parent) = stack.pop() {
+            for item in self.creases.iter() {
+                if item.parent == Some(parent) {
+                    stack.push(item.id);
+                    descendants.push(&item.crease);
+                }
+            }
+        }
+        descendants.into_iter()
+    }
+
+    /// Returns every crease whose range covers `row`, ordered from the innermost
+    /// (smallest) enclosing crease outward to the outermost.
+    pub fn creases_containing<'a>(
+        &'a self,
+        row: MultiBufferRow,
+        snapshot: &'a MultiBufferSnapshot,
+    ) -> Vec<&'a Crease> {
+        let mut containing = self
+            .creases
+            .iter()
+            .filter(|item| {
+                let range = item.crease.range();
+                let start = range.start.to_point(snapshot).row;
+                let end = range.end.to_point(snapshot).row;
+                start <= row.0 && row.0 <= end
+            })
+            .map(|item| &item.crease)
+            .collect::<Vec<_>>();
+
+        // Order by actual containment rather than row-span width: the innermost
+        // crease has the latest start and, among those, the earliest end. Using
+        // the span width would misorder a wider-but-not-enclosing sibling ahead
+        // of a true ancestor.
+        containing.sort_by(|a, b| {
+            let a = a.range();
+            let b = b.range();
+            let a_start = a.start.to_point(snapshot);
+            let b_start = b.start.to_point(snapshot);
+            b_start
+                .cmp(&a_start)
+                .then_with(|| a.end.to_point(snapshot).cmp(&b.end.to_point(snapshot)))
+        });
+        containing
+    }
+
     pub fn crease_items_with_offsets(
         &self,
         snapshot: &MultiBufferSnapshot,
@@ -123,6 +222,29 @@ type RenderToggleFn = Arc<
 >;
 type RenderTrailerFn =
     Arc<dyn Send + Sync + Fn(MultiBufferRow, bool, &mut WindowContext) -> AnyElement>;
+type RenderInlineFn = Arc<dyn Send + Sync + Fn(&InlineImage, &mut WindowContext) -> AnyElement>;
+
+/// A raster image that collapses a text range into a rendered picture.
+///
+/// The image is decoded off the main thread; [`InlineImage::texture`] returns
+/// `None` until the decode completes, at which point the render closure swaps
+/// the spinner placeholder for the real texture.
+pub struct InlineImage {
+    size: Size<Pixels>,
+    texture: Arc<Mutex<Option<Arc<RenderImage>>>>,
+}
+
+impl InlineImage {
+    /// The target display size the image is resized to.
+    pub fn size(&self) -> Size<Pixels> {
+        self.size
+    }
+
+    /// The decoded texture, or `None` while the background decode is in flight.
+    pub fn texture(&self) -> Option<Arc<RenderImage>> {
+        self.texture.lock().clone()
+    }
+}
 
 #[derive(Clone)]
 pub enum Crease {
@@ -133,6 +255,11 @@ pub enum Crease {
         render_trailer: RenderTrailerFn,
         metadata: Option<CreaseMetadata>,
     },
+    Inline {
+        range: Range<Anchor>,
+        image: Arc<InlineImage>,
+        render_image: RenderInlineFn,
+    },
 }
 
 /// Metadata about a [`Crease`], that is used for serialization.
@@ -142,6 +269,15 @@ pub struct CreaseMetadata {
     pub label: SharedString,
 }
 
+/// A persisted [`Crease::Fold`]: its range as buffer point offsets plus its
+/// metadata. Produced by [`CreaseSnapshot::serialize`] and rehydrated by
+/// [`CreaseMap::restore`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerializedCrease {
+    pub range: Range<Point>,
+    pub metadata: Option<CreaseMetadata>,
+}
+
 impl Crease {
     pub fn new<RenderToggle, ToggleElement, RenderTrailer, TrailerElement>(
         range: Range<Anchor>,
@@ -181,6 +317,67 @@ impl Crease {
         }
     }
 
+    /// Creates an inline crease that replaces `range` with a rendered raster
+    /// image decoded from `source`.
+    ///
+    /// `source` may be the raw bytes of an encoded image or the bytes of an
+    /// `data:` URI (e.g. a Markdown `![](data:image/png;base64,…)` link); in the
+    /// latter case the base64 payload is decoded first. Decoding and resizing to
+    /// `size` happen on a background thread, and the render closure shows a
+    /// spinner until the texture is ready.
+    pub fn inline(
+        range: Range<Anchor>,
+        source: impl Into<Arc<[u8]>>,
+        size: Size<Pixels>,
+        cx: &mut WindowContext,
+    ) -> Self {
+        let source = source.into();
+        let texture = Arc::new(Mutex::new(None));
+        let image = Arc::new(InlineImage {
+            size,
+            texture: texture.clone(),
+        });
+
+        cx.spawn(|mut cx| async move {
+            let decoded = cx
+                .background_executor()
+                .spawn(async move { decode_inline_image(&source, size) })
+                .await;
+            if let Some(decoded) = decoded {
+                *texture.lock() = Some(decoded);
+                // Repaint so the render closure swaps the spinner for the texture;
+                // without this the spinner lingers until an unrelated repaint.
+                cx.refresh().ok();
+            }
+        })
+        .detach();
+
+        Crease::Inline {
+            range,
+            image,
+            render_image: Arc::new(|image, cx| render_inline_image(image, cx)),
+        }
+    }
+
+    /// Creates a fold crease over `source` — the raw bytes of colored log or
+    /// terminal output — whose placeholder renders the folded region as a
+    /// compact colorized one-liner.
+    ///
+    /// The SGR/ANSI escape sequences in `source` are interpreted once (see
+    /// [`parse_ansi`]) and the resulting styled runs drive the `render_toggle`
+    /// closure, so the folded block keeps its colors instead of showing a
+    /// generic `…`. Incomplete sequences at the range boundary are dropped.
+    pub fn ansi(range: Range<Anchor>, source: impl AsRef<[u8]>) -> Self {
+        let runs = Arc::new(parse_ansi(source.as_ref()));
+        Crease::Fold {
+            range,
+            placeholder: FoldPlaceholder::default(),
+            render_toggle: Arc::new(move |_row, _folded, _toggle, _cx| render_ansi_runs(&runs)),
+            render_trailer: Arc::new(|_row, _folded, _cx| Empty.into_any_element()),
+            metadata: None,
+        }
+    }
+
     pub fn with_metadata(self, metadata: CreaseMetadata) -> Self {
         match self {
             Crease::Fold {
@@ -196,13 +393,450 @@ impl Crease {
                 render_trailer,
                 metadata: Some(metadata),
             },
+            // Inline creases carry no fold metadata.
+            crease @ Crease::Inline { .. } => crease,
         }
     }
 
     pub fn range(&self) -> &Range<Anchor> {
         match self {
-            Crease::Fold { range, .. } => range,
+            Crease::Fold { range, .. } | Crease::Inline { range, .. } => range,
+        }
+    }
+
+    /// Computes a syntax-highlighted one-line summary of the first meaningful
+    /// line of this crease's folded region, so the placeholder can show what a
+    /// folded block contains rather than a generic `…`.
+    ///
+    /// The summary starts at the crease's [`range`](Self::range) and skips
+    /// leading blank and comment-only lines. The resulting styled runs are
+    /// truncated to `max_chars`, with a trailing ellipsis when the line is
+    /// longer.
+    pub fn fold_summary(
+        &self,
+        snapshot: &MultiBufferSnapshot,
+        theme: &SyntaxTheme,
+        max_chars: usize,
+    ) -> Option<Vec<StyledRun>> {
+        let range = self.range();
+        let highlighter = SyntaxHighlighter::new(snapshot, theme);
+        highlighter.summarize(
+            range.start.to_point(snapshot).row,
+            range.end.to_point(snapshot).row,
+            max_chars,
+        )
+    }
+}
+
+/// A run of summary text tagged with the [`HighlightStyle`] it should render
+/// with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyledRun {
+    pub text: String,
+    pub style: HighlightStyle,
+}
+
+/// Tokenizes a single line into syntax-highlighted [`StyledRun`]s using the
+/// buffer's language scope and the active [`SyntaxTheme`].
+struct SyntaxHighlighter<'a> {
+    snapshot: &'a MultiBufferSnapshot,
+    theme: &'a SyntaxTheme,
+}
+
+impl<'a> SyntaxHighlighter<'a> {
+    fn new(snapshot: &'a MultiBufferSnapshot, theme: &'a SyntaxTheme) -> Self {
+        Self { snapshot, theme }
+    }
+
+    /// Highlights the first meaningful line in the inclusive row range,
+    /// returning `None` when every line is blank or comment-only.
+    fn summarize(&self, start_row: u32, end_row: u32, max_chars: usize) -> Option<Vec<StyledRun>> {
+        let row = self.first_meaningful_row(start_row, end_row)?;
+        let line_start = Point::new(row, 0);
+        let line_end = Point::new(row, self.snapshot.line_len(MultiBufferRow(row)));
+
+        let mut runs = Vec::new();
+        let mut remaining = max_chars;
+        // `chunks()` splits the line at token boundaries, so interior chunks
+        // carry the spacing between tokens. Only strip the leading indentation
+        // once — before the first visible run — or that inter-token spacing is
+        // destroyed and `fn foo()` collapses to `fnfoo()`.
+        let mut seen_content = false;
+        for chunk in self.snapshot.chunks(line_start..line_end, true) {
+            let style = chunk
+                .syntax_highlight_id
+                .and_then(|id| id.style(self.theme))
+                .unwrap_or_default();
+            let text = chunk.text.trim_matches('\n');
+            let text = if seen_content { text } else { text.trim_start() };
+            if text.is_empty() {
+                continue;
+            }
+            if remaining == 0 {
+                // The line goes on past `max_chars`: end the last run with the
+                // ellipsis in place of its final character.
+                if let Some(last) = runs.last_mut() {
+                    last.text.pop();
+                    last.text.push('…');
+                }
+                break;
+            }
+            seen_content = true;
+
+            let count = text.chars().count();
+            if count <= remaining {
+                runs.push(StyledRun {
+                    text: text.to_string(),
+                    style,
+                });
+                remaining -= count;
+            } else {
+                let truncated: String = text.chars().take(remaining.saturating_sub(1)).collect();
+                runs.push(StyledRun {
+                    text: format!("{truncated}…"),
+                    style,
+                });
+                remaining = 0;
+            }
+        }
+
+        (!runs.is_empty()).then_some(runs)
+    }
+
+    fn first_meaningful_row(&self, start_row: u32, end_row: u32) -> Option<u32> {
+        (start_row..=end_row).find(|&row| {
+            let line = self.line_text(row);
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !self.is_comment_only(row, trimmed)
+        })
+    }
+
+    fn line_text(&self, row: u32) -> String {
+        let len = self.snapshot.line_len(MultiBufferRow(row));
+        self.snapshot
+            .text_for_range(Point::new(row, 0)..Point::new(row, len))
+            .collect()
+    }
+
+    fn is_comment_only(&self, row: u32, trimmed: &str) -> bool {
+        let Some(scope) = self.snapshot.language_scope_at(Point::new(row, 0)) else {
+            return false;
+        };
+        scope
+            .line_comment_prefixes()
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix.as_ref()))
+    }
+}
+
+/// Interprets the SGR/ANSI escape sequences in `bytes` and returns the visible
+/// text split into [`StyledRun`]s, so colored log or terminal output folded
+/// into a crease can be rendered as styled spans.
+///
+/// Incomplete escape sequences at the end of the input — which happen when a
+/// crease range slices through the middle of a sequence — are dropped rather
+/// than emitted as garbage.
+pub fn parse_ansi(bytes: &[u8]) -> Vec<StyledRun> {
+    let text = String::from_utf8_lossy(bytes);
+    let chars: Vec<char> = text.chars().collect();
+
+    let mut runs: Vec<StyledRun> = Vec::new();
+    let mut pending = String::new();
+    let mut state = AnsiState::default();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\u{1b}' {
+            if chars.get(i + 1) == Some(&'[') {
+                match parse_sgr(&chars[i..]) {
+                    Some((params, final_byte, consumed)) => {
+                        flush_run(&mut runs, &mut pending, &state);
+                        if final_byte == 'm' {
+                            state.apply(&params);
+                        }
+                        i += consumed;
+                    }
+                    // Incomplete sequence at a range boundary: stop here.
+                    None => break,
+                }
+            } else {
+                // A lone escape we don't model; skip just the escape byte.
+                i += 1;
+            }
+            continue;
+        }
+        pending.push(chars[i]);
+        i += 1;
+    }
+    flush_run(&mut runs, &mut pending, &state);
+    runs
+}
+
+/// Returns the folded range with every ANSI escape sequence removed, for
+/// consumers that want plain text rather than styled spans.
+pub fn strip_ansi(bytes: &[u8]) -> String {
+    parse_ansi(bytes).into_iter().map(|run| run.text).collect()
+}
+
+/// Renders ANSI-parsed [`StyledRun`]s into a single horizontal row of colored
+/// spans for a folded crease's placeholder.
+fn render_ansi_runs(runs: &[StyledRun]) -> AnyElement {
+    use ui::prelude::*;
+
+    let mut row = div().flex().flex_row().items_center();
+    for run in runs {
+        let mut span = div().child(run.text.clone());
+        if let Some(color) = run.style.color {
+            span = span.text_color(color);
+        }
+        if let Some(background) = run.style.background_color {
+            span = span.bg(background);
+        }
+        if run.style.font_weight == Some(FontWeight::BOLD) {
+            span = span.font_weight(FontWeight::BOLD);
+        }
+        row = row.child(span);
+    }
+    row.into_any_element()
+}
+
+fn flush_run(runs: &mut Vec<StyledRun>, pending: &mut String, state: &AnsiState) {
+    if !pending.is_empty() {
+        runs.push(StyledRun {
+            text: std::mem::take(pending),
+            style: state.to_style(),
+        });
+    }
+}
+
+/// Parses a CSI sequence starting at `chars[0]` (the escape byte), returning the
+/// numeric parameters, the final byte, and the number of chars consumed.
+/// Returns `None` if the sequence is truncated.
+///
+/// A sequence containing parameter or intermediate bytes that are not modelled
+/// (such as the `?` of `ESC[?25h`) is consumed whole and returns no
+/// parameters. A sequence broken off by a byte that cannot occur in a CSI
+/// sequence ends before that byte, which is returned as the final byte but not
+/// consumed.
+fn parse_sgr(chars: &[char]) -> Option<(Vec<u16>, char, usize)> {
+    let mut params = Vec::new();
+    let mut number = String::new();
+    let mut modelled = true;
+    let mut j = 2; // skip ESC and '['
+    while let Some(&c) = chars.get(j) {
+        if c.is_ascii_digit() {
+            number.push(c);
+        } else if c == ';' {
+            params.push(number.parse().unwrap_or(0));
+            number.clear();
+        } else if (' '..='?').contains(&c) {
+            modelled = false;
+        } else if ('@'..='~').contains(&c) {
+            if !modelled {
+                return Some((Vec::new(), c, j + 1));
+            }
+            if !number.is_empty() {
+                params.push(number.parse().unwrap_or(0));
+            } else if params.is_empty() {
+                // A bare `ESC[m` resets all attributes.
+                params.push(0);
+            }
+            return Some((params, c, j + 1));
+        } else {
+            return Some((Vec::new(), c, j));
         }
+        j += 1;
+    }
+    None
+}
+
+/// Text attributes accumulated while walking SGR escape sequences.
+#[derive(Clone, Default)]
+struct AnsiState {
+    foreground: Option<Hsla>,
+    background: Option<Hsla>,
+    bold: bool,
+    underline: bool,
+}
+
+impl AnsiState {
+    fn to_style(&self) -> HighlightStyle {
+        HighlightStyle {
+            color: self.foreground,
+            background_color: self.background,
+            font_weight: self.bold.then_some(FontWeight::BOLD),
+            underline: self.underline.then(|| UnderlineStyle {
+                thickness: px(1.),
+                color: self.foreground,
+                wavy: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn apply(&mut self, params: &[u16]) {
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *self = AnsiState::default(),
+                1 => self.bold = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(ansi_color(params[i] - 30)),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(ansi_color(params[i] - 40)),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(ansi_color(params[i] - 90 + 8)),
+                100..=107 => self.background = Some(ansi_color(params[i] - 100 + 8)),
+                38 => self.foreground = parse_extended_color(params, &mut i),
+                48 => self.background = parse_extended_color(params, &mut i),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Parses a `38`/`48` extended-color selector (`5;n` for 256-color, `2;r;g;b`
+/// for truecolor), advancing `i` past the consumed parameters.
+fn parse_extended_color(params: &[u16], i: &mut usize) -> Option<Hsla> {
+    match params.get(*i + 1) {
+        Some(5) => {
+            let index = *params.get(*i + 2)?;
+            *i += 2;
+            Some(indexed_color(index))
+        }
+        Some(2) => {
+            let r = *params.get(*i + 2)? as u32;
+            let g = *params.get(*i + 3)? as u32;
+            let b = *params.get(*i + 4)? as u32;
+            *i += 4;
+            Some(rgb((r << 16) | (g << 8) | b).into())
+        }
+        _ => None,
+    }
+}
+
+/// Maps one of the 16 base ANSI colors to an [`Hsla`].
+fn ansi_color(index: u16) -> Hsla {
+    let hex = match index {
+        0 => 0x000000,
+        1 => 0xcd0000,
+        2 => 0x00cd00,
+        3 => 0xcdcd00,
+        4 => 0x0000ee,
+        5 => 0xcd00cd,
+        6 => 0x00cdcd,
+        7 => 0xe5e5e5,
+        8 => 0x7f7f7f,
+        9 => 0xff0000,
+        10 => 0x00ff00,
+        11 => 0xffff00,
+        12 => 0x5c5cff,
+        13 => 0xff00ff,
+        14 => 0x00ffff,
+        _ => 0xffffff,
+    };
+    rgb(hex).into()
+}
+
+/// Maps a 256-color palette index to an [`Hsla`], covering the 16 base colors,
+/// the 6×6×6 color cube, and the grayscale ramp.
+fn indexed_color(index: u16) -> Hsla {
+    match index {
+        0..=15 => ansi_color(index),
+        16..=231 => {
+            let i = index - 16;
+            let component = |c: u16| -> u32 {
+                if c == 0 {
+                    0
+                } else {
+                    (55 + c * 40) as u32
+                }
+            };
+            let r = component(i / 36);
+            let g = component((i / 6) % 6);
+            let b = component(i % 6);
+            rgb((r << 16) | (g << 8) | b).into()
+        }
+        _ => {
+            let level = (8 + (index - 232) * 10) as u32;
+            rgb((level << 16) | (level << 8) | level).into()
+        }
+    }
+}
+
+/// Clamps a persisted point into the current buffer, pinning the column to the
+/// target row's length.
+fn clamp_point(point: Point, snapshot: &MultiBufferSnapshot) -> Point {
+    let row = point.row.min(snapshot.max_point().row);
+    let column = point.column.min(snapshot.line_len(MultiBufferRow(row)));
+    Point::new(row, column)
+}
+
+/// Decodes `source` (raw image bytes or a `data:` URI) and resizes it to `size`.
+fn decode_inline_image(source: &[u8], size: Size<Pixels>) -> Option<Arc<RenderImage>> {
+    use image::{Frame, RgbaImage};
+
+    let bytes = decode_data_uri(source);
+    let bytes = bytes.as_deref().unwrap_or(source);
+
+    let image = image::load_from_memory(bytes).ok()?;
+    let resized = image.resize_exact(
+        size.width.0.max(1.0) as u32,
+        size.height.0.max(1.0) as u32,
+        image::imageops::FilterType::Triangle,
+    );
+    let mut buffer: RgbaImage = resized.into_rgba8();
+    premultiply_bgra(&mut buffer);
+
+    Some(Arc::new(RenderImage::new(smallvec::smallvec![Frame::new(
+        buffer
+    )])))
+}
+
+/// Converts a straight-alpha RGBA image into the premultiplied BGRA layout gpui
+/// textures expect: channels are reordered R↔B and the color channels are
+/// premultiplied by alpha, so transparent and semi-transparent pixels blend
+/// correctly.
+fn premultiply_bgra(buffer: &mut image::RgbaImage) {
+    for pixel in buffer.pixels_mut() {
+        pixel.0.swap(0, 2);
+        let alpha = pixel.0[3] as u16;
+        for channel in &mut pixel.0[0..3] {
+            *channel = ((*channel as u16 * alpha) / 255) as u8;
+        }
+    }
+}
+
+/// Splits out and base64-decodes the payload of a `data:` URI, returning `None`
+/// if `source` is not such a URI.
+fn decode_data_uri(source: &[u8]) -> Option<Vec<u8>> {
+    use base64::Engine as _;
+
+    let source = std::str::from_utf8(source).ok()?;
+    let rest = source.strip_prefix("data:")?;
+    let payload = rest.split_once("base64,").map(|(_, payload)| payload)?;
+    base64::engine::general_purpose::STANDARD
+        .decode(payload.trim())
+        .ok()
+}
+
+/// Renders the decoded texture, falling back to a spinner while the background
+/// decode is still in flight.
+fn render_inline_image(image: &InlineImage, _cx: &mut WindowContext) -> AnyElement {
+    use gpui::img;
+    use ui::prelude::*;
+
+    let size = image.size();
+    if let Some(texture) = image.texture() {
+        img(texture).w(size.width).h(size.height).into_any_element()
+    } else {
+        div()
+            .w(size.width)
+            .h(size.height)
+            .child(ui::Icon::new(IconName::ArrowCircle))
+            .into_any_element()
     }
 }
 
@@ -213,6 +847,11 @@ impl std::fmt::Debug for Crease {
                 .debug_struct("Crease::Fold")
                 .field("range", range)
                 .finish(),
+            Crease::Inline { range, image, .. } => f
+                .debug_struct("Crease::Inline")
+                .field("range", range)
+                .field("size", &image.size())
+                .finish(),
         }
     }
 }
@@ -221,6 +860,9 @@ impl std::fmt::Debug for Crease {
 struct CreaseItem {
     id: CreaseId,
     crease: Crease,
+    /// The smallest existing crease that encloses this one at insert time, used
+    /// to answer nested fold-children queries.
+    parent: Option<CreaseId>,
 }
 
 impl CreaseMap {
@@ -243,8 +885,9 @@ impl CreaseMap {
 
                 let id = self.next_id;
                 self.next_id.0 += 1;
+                let parent = self.enclosing_crease(&crease_range, snapshot);
                 self.id_to_range.insert(id, crease_range);
-                new_creases.push(CreaseItem { crease, id }, snapshot);
+                new_creases.push(CreaseItem { crease, id, parent }, snapshot);
                 new_ids.push(id);
             }
             new_creases.append(cursor.suffix(snapshot), snapshot);
@@ -253,6 +896,77 @@ impl CreaseMap {
         new_ids
     }
 
+    /// Locates the tightest already-registered crease whose range fully spans
+    /// `range`, which becomes the parent of a crease inserted at `range`.
+    fn enclosing_crease(
+        &self,
+        range: &Range<Anchor>,
+        snapshot: &MultiBufferSnapshot,
+    ) -> Option<CreaseId> {
+        let target = range.start.to_point(snapshot)..range.end.to_point(snapshot);
+        let mut candidates = self
+            .id_to_range
+            .iter()
+            .filter_map(|(id, candidate)| {
+                let candidate =
+                    candidate.start.to_point(snapshot)..candidate.end.to_point(snapshot);
+                (candidate.start <= target.start
+                    && candidate.end >= target.end
+                    && candidate != target)
+                    .then_some((*id, candidate))
+            })
+            .collect::<Vec<_>>();
+
+        // Pick the tightest enclosing crease with a total order so the parent is
+        // stable regardless of the `HashMap`'s iteration order: prefer the latest
+        // start, then the earliest end, then the lowest `CreaseId` as a final
+        // tie-break when two creases span the target without nesting.
+        candidates.sort_by(|(a_id, a), (b_id, b)| {
+            b.start
+                .cmp(&a.start)
+                .then_with(|| a.end.cmp(&b.end))
+                .then_with(|| a_id.cmp(b_id))
+        });
+        candidates.into_iter().next().map(|(id, _)| id)
+    }
+
+    /// Re-anchors and re-inserts creases previously captured by
+    /// [`CreaseSnapshot::serialize`], assigning fresh [`CreaseId`]s.
+    ///
+    /// Saved `Point` ranges are resolved against the current buffer: columns are
+    /// clamped to the current line length, and entries whose start row no longer
+    /// exists are dropped.
+    pub fn restore(
+        &mut self,
+        serialized: impl IntoIterator<Item = SerializedCrease>,
+        snapshot: &MultiBufferSnapshot,
+    ) -> Vec<CreaseId> {
+        let max_point = snapshot.max_point();
+        let creases = serialized
+            .into_iter()
+            .filter_map(|serialized| {
+                if serialized.range.start.row > max_point.row {
+                    return None;
+                }
+                let start = clamp_point(serialized.range.start, snapshot);
+                let end = clamp_point(serialized.range.end, snapshot);
+                let range = snapshot.anchor_before(start)..snapshot.anchor_after(end);
+                let mut crease = Crease::new(
+                    range,
+                    FoldPlaceholder::default(),
+                    |_row, _folded, _toggle, _cx| Empty,
+                    |_row, _folded, _cx| Empty,
+                );
+                if let Some(metadata) = serialized.metadata {
+                    crease = crease.with_metadata(metadata);
+                }
+                Some(crease)
+            })
+            .collect::<Vec<_>>();
+
+        self.insert(creases, snapshot)
+    }
+
     pub fn remove(
         &mut self,
         ids: impl IntoIterator<Item = CreaseId>,
@@ -268,6 +982,29 @@ impl CreaseMap {
             AnchorRangeExt::cmp(a_range, b_range, snapshot).then(b_id.cmp(a_id))
         });
 
+        // Children of a removed crease must be re-parented to the removed
+        // crease's own parent (the grandparent), otherwise their `parent` keeps
+        // pointing at a dead `CreaseId` and `children`/`descendants` queries lose
+        // the orphaned subtree. Capture each removed crease's parent first, then
+        // resolve any parent that was removed up to the nearest survivor.
+        let removed_ids: HashSet<CreaseId> = removals.iter().map(|(id, _)| *id).collect();
+        let mut parent_of_removed: HashMap<CreaseId, Option<CreaseId>> = HashMap::default();
+        for item in self.snapshot.creases.iter() {
+            if removed_ids.contains(&item.id) {
+                parent_of_removed.insert(item.id, item.parent);
+            }
+        }
+        let resolve_parent = |mut parent: Option<CreaseId>| {
+            while let Some(id) = parent {
+                if removed_ids.contains(&id) {
+                    parent = parent_of_removed.get(&id).copied().flatten();
+                } else {
+                    break;
+                }
+            }
+            parent
+        };
+
         self.snapshot.creases = {
             let mut new_creases = SumTree::new(snapshot);
             let mut cursor = self.snapshot.creases.cursor::<ItemSummary>(snapshot);
@@ -279,12 +1016,29 @@ impl CreaseMap {
                     if item.id == id {
                         break;
                     } else {
-                        new_creases.push(item.clone(), snapshot);
+                        let mut item = item.clone();
+                        item.parent = resolve_parent(item.parent);
+                        new_creases.push(item, snapshot);
                     }
                 }
             }
 
-            new_creases.append(cursor.suffix(snapshot), snapshot);
+            while let Some(item) = cursor.item() {
+                let mut item = item.clone();
+                item.parent = resolve_parent(item.parent);
+                new_creases.push(item, snapshot);
+                cursor.next(snapshot);
+            }
+            // `cursor.slice` copies the creases lying between two removals
+            // unchanged, so resolve their parents as well.
+            let new_creases = SumTree::from_iter(
+                new_creases.iter().map(|item| {
+                    let mut item = item.clone();
+                    item.parent = resolve_parent(item.parent);
+                    item
+                }),
+                snapshot,
+            );
             new_creases
         };
     }
@@ -440,4 +1194,220 @@ mod test {
         let creases: Vec<_> = crease_snapshot.creases_in_range(range, &snapshot).collect();
         assert_eq!(creases.len(), 0);
     }
+
+    #[gpui::test]
+    fn test_serialize_and_restore_creases(cx: &mut AppContext) {
+        let text = "line1\nline2\nline3\nline4\nline5";
+        let buffer = MultiBuffer::build_simple(text, cx);
+        let snapshot = buffer.read_with(cx, |buffer, cx| buffer.snapshot(cx));
+        let mut crease_map = CreaseMap::new(&snapshot);
+
+        let creases = [Crease::new(
+            snapshot.anchor_before(Point::new(1, 0))..snapshot.anchor_after(Point::new(1, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        )
+        .with_metadata(CreaseMetadata {
+            icon: IconName::ArrowCircle,
+            label: "block".into(),
+        })];
+        crease_map.insert(creases, &snapshot);
+
+        let serialized = crease_map.snapshot().serialize(&snapshot);
+        assert_eq!(serialized.len(), 1);
+        assert_eq!(serialized[0].range, Point::new(1, 0)..Point::new(1, 5));
+        assert_eq!(serialized[0].metadata.as_ref().unwrap().label, "block");
+
+        // Restoring into a fresh map re-anchors the saved range.
+        let mut restored_map = CreaseMap::new(&snapshot);
+        let ids = restored_map.restore(serialized, &snapshot);
+        assert_eq!(ids.len(), 1);
+        assert!(restored_map
+            .snapshot()
+            .query_row(MultiBufferRow(1), &snapshot)
+            .is_some());
+
+        // Entries whose rows no longer exist are dropped.
+        let mut empty_map = CreaseMap::new(&snapshot);
+        let dropped = empty_map.restore(
+            vec![SerializedCrease {
+                range: Point::new(99, 0)..Point::new(99, 5),
+                metadata: None,
+            }],
+            &snapshot,
+        );
+        assert!(dropped.is_empty());
+    }
+
+    #[gpui::test]
+    fn test_nested_creases(cx: &mut AppContext) {
+        let text = "line1\nline2\nline3\nline4\nline5\nline6\nline7";
+        let buffer = MultiBuffer::build_simple(text, cx);
+        let snapshot = buffer.read_with(cx, |buffer, cx| buffer.snapshot(cx));
+        let mut crease_map = CreaseMap::new(&snapshot);
+
+        let outer = Crease::new(
+            snapshot.anchor_before(Point::new(0, 0))..snapshot.anchor_after(Point::new(6, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let inner = Crease::new(
+            snapshot.anchor_before(Point::new(1, 0))..snapshot.anchor_after(Point::new(4, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let leaf = Crease::new(
+            snapshot.anchor_before(Point::new(2, 0))..snapshot.anchor_after(Point::new(3, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let ids = crease_map.insert([outer, inner, leaf], &snapshot);
+        let (outer_id, inner_id) = (ids[0], ids[1]);
+
+        let crease_snapshot = crease_map.snapshot();
+
+        // The outer crease has one direct child and two descendants.
+        let children: Vec<_> = crease_snapshot.children(outer_id, &snapshot).collect();
+        assert_eq!(children.len(), 1);
+        let descendants: Vec<_> = crease_snapshot.descendants(outer_id, &snapshot).collect();
+        assert_eq!(descendants.len(), 2);
+        assert_eq!(
+            crease_snapshot.children(inner_id, &snapshot).count(),
+            1,
+            "inner crease should parent the leaf"
+        );
+
+        // Row 2 is covered by all three creases, innermost first.
+        let containing = crease_snapshot.creases_containing(MultiBufferRow(2), &snapshot);
+        assert_eq!(containing.len(), 3);
+        assert_eq!(containing[0].range().start.to_point(&snapshot).row, 2);
+        assert_eq!(containing[2].range().start.to_point(&snapshot).row, 0);
+    }
+
+    #[gpui::test]
+    fn test_fold_summary(cx: &mut AppContext) {
+        let text =
+            "\n\n    fn demonstrate_a_fairly_long_function_name() {\n    body\n}";
+        let buffer = MultiBuffer::build_simple(text, cx);
+        let snapshot = buffer.read_with(cx, |buffer, cx| buffer.snapshot(cx));
+        let theme = SyntaxTheme::default();
+
+        let crease = Crease::new(
+            snapshot.anchor_before(Point::new(0, 0))..snapshot.anchor_after(Point::new(4, 1)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+
+        // Leading blank lines are skipped and the first meaningful line's
+        // indentation is trimmed.
+        let runs = crease.fold_summary(&snapshot, &theme, 100).unwrap();
+        let summary: String = runs.iter().map(|run| run.text.as_str()).collect();
+        assert!(summary.starts_with("fn demonstrate"));
+
+        // A narrow width truncates to exactly `max_chars`, ending in an ellipsis.
+        let runs = crease.fold_summary(&snapshot, &theme, 6).unwrap();
+        let truncated: String = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(truncated.chars().count(), 6);
+        assert!(truncated.ends_with('…'));
+    }
+
+    #[gpui::test]
+    fn test_remove_reparents_children(cx: &mut AppContext) {
+        let text = "line1\nline2\nline3\nline4\nline5\nline6\nline7";
+        let buffer = MultiBuffer::build_simple(text, cx);
+        let snapshot = buffer.read_with(cx, |buffer, cx| buffer.snapshot(cx));
+        let mut crease_map = CreaseMap::new(&snapshot);
+
+        let outer = Crease::new(
+            snapshot.anchor_before(Point::new(0, 0))..snapshot.anchor_after(Point::new(6, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let inner = Crease::new(
+            snapshot.anchor_before(Point::new(1, 0))..snapshot.anchor_after(Point::new(4, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let leaf = Crease::new(
+            snapshot.anchor_before(Point::new(2, 0))..snapshot.anchor_after(Point::new(3, 5)),
+            FoldPlaceholder::test(),
+            |_row, _folded, _toggle, _cx| div(),
+            |_row, _folded, _cx| div(),
+        );
+        let ids = crease_map.insert([outer, inner, leaf], &snapshot);
+        let (outer_id, inner_id) = (ids[0], ids[1]);
+
+        // Removing the intermediate crease re-parents the leaf to the outer
+        // crease rather than orphaning it.
+        crease_map.remove([inner_id], &snapshot);
+        let crease_snapshot = crease_map.snapshot();
+        assert_eq!(crease_snapshot.children(outer_id, &snapshot).count(), 1);
+        assert_eq!(crease_snapshot.descendants(outer_id, &snapshot).count(), 1);
+    }
+
+    #[test]
+    fn test_parse_ansi_truecolor() {
+        let runs = parse_ansi(b"\x1b[38;2;255;0;0mred\x1b[0m");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "red");
+        assert_eq!(runs[0].style.color, Some(rgb(0xff0000).into()));
+    }
+
+    #[test]
+    fn test_parse_ansi_256_color() {
+        let runs = parse_ansi(b"\x1b[38;5;1mx\x1b[0m");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "x");
+        assert_eq!(runs[0].style.color, Some(ansi_color(1)));
+    }
+
+    #[test]
+    fn test_parse_ansi_incomplete_sequence_at_boundary() {
+        // A sequence sliced at the crease boundary is dropped, not emitted as
+        // garbage text.
+        let runs = parse_ansi(b"ok\x1b[38;2;1");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "ok");
+    }
+
+    #[test]
+    fn test_strip_ansi_round_trip() {
+        assert_eq!(strip_ansi(b"\x1b[31mred\x1b[0m text"), "red text");
+        assert_eq!(strip_ansi(b"plain"), "plain");
+    }
+
+    #[test]
+    fn test_premultiply_bgra() {
+        use image::{Rgba, RgbaImage};
+
+        let mut buffer = RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 128]));
+        premultiply_bgra(&mut buffer);
+        // R↔B swapped (so blue is now 255) then color channels premultiplied by
+        // alpha/255: the half-transparent pixel must not stay fully saturated.
+        assert_eq!(buffer.get_pixel(0, 0).0, [0, 0, 128, 128]);
+    }
+
+    #[test]
+    fn test_decode_inline_image_from_data_uri() {
+        use base64::Engine as _;
+        use image::{ImageFormat, Rgba, RgbaImage};
+        use std::io::Cursor;
+
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&png);
+        let uri = format!("data:image/png;base64,{encoded}");
+
+        let decoded = decode_inline_image(uri.as_bytes(), Size::new(px(4.), px(4.)));
+        assert!(decoded.is_some());
+    }
 }